@@ -1,4 +1,4 @@
-pub use std::thread::{current, sleep, Result, Thread, ThreadId};
+pub use std::thread::{sleep, Result};
 use std::{
     cell::UnsafeCell,
     fmt,
@@ -7,18 +7,25 @@ use std::{
     panic::{catch_unwind, AssertUnwindSafe},
     rc::Rc,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
+pub use pool::{BroadcastContext, ThreadPool};
 use scoped::ScopeData;
 pub use scoped::{scope, Scope, ScopedJoinHandle};
 use signal::Signal;
+pub use thread::{current, park, park_timeout, Thread, ThreadId};
+pub use tls::{WorkerLocalCell, WorkerLocalRef};
 use utils::SpinLockMutex;
 pub use utils::{available_parallelism, get_wasm_bindgen_shim_script_path, get_worker_script, is_web_worker_thread};
 use wasm_bindgen::prelude::*;
 use web_sys::{DedicatedWorkerGlobalScope, Worker, WorkerOptions, WorkerType};
 
+mod pool;
 mod scoped;
 mod signal;
+mod thread;
+mod tls;
 mod utils;
 
 struct WebWorkerContext {
@@ -26,10 +33,18 @@ struct WebWorkerContext {
 }
 
 /// Entry point for web workers
+///
+/// If `ctx.func` aborts rather than returning (see the double-panic note on
+/// `Packet`'s `Drop` impl below), this never reaches `ThreadComplete.post()`
+/// and a pooled worker's slot is never reclaimed - see the limitation noted
+/// in the `pool` module's docs.
 #[wasm_bindgen]
 pub fn wasm_thread_entry_point(ptr: u32) {
     let ctx = unsafe { Box::from_raw(ptr as *mut WebWorkerContext) };
     (ctx.func)();
+    // Run this job's worker-local destructors now, before the worker is
+    // handed its next one (which, if pooled, may run on this very thread).
+    tls::run_destructors();
     WorkerMessage::ThreadComplete.post();
 }
 
@@ -37,11 +52,19 @@ pub fn wasm_thread_entry_point(ptr: u32) {
 struct BuilderRequest {
     builder: Builder,
     context: WebWorkerContext,
+    // Scoped threads need a dedicated, one-shot worker so that their
+    // lifetime is easy to reason about; everything else goes through the
+    // reusable pool. See `Builder::spawn_unchecked_`.
+    pooled: bool,
 }
 
 impl BuilderRequest {
     pub unsafe fn spawn(self) {
-        self.builder.spawn_for_context(self.context);
+        if self.pooled {
+            ThreadPool::global().spawn_context(self.context);
+        } else {
+            self.builder.spawn_for_context(self.context);
+        }
     }
 }
 
@@ -186,6 +209,15 @@ impl Builder {
         let my_signal = Arc::new(Signal::new());
         let their_signal = my_signal.clone();
 
+        let thread = thread::Thread::new(self.name.clone(), self.prefix.clone());
+        let their_thread = thread.clone();
+
+        // Scoped threads get a dedicated one-shot worker (their lifetime is
+        // bounded by the scope), everything else is dispatched onto the
+        // reusable global pool so we don't pay worker startup cost on every
+        // spawn.
+        let pooled = scope_data.is_none();
+
         let my_packet: Arc<Packet<'scope, T>> = Arc::new(Packet {
             scope: scope_data,
             result: UnsafeCell::new(None),
@@ -219,6 +251,9 @@ impl Builder {
 
         let f = MaybeDangling::new(f);
         let main = Box::new(move || {
+            // Make `current()` resolve to this thread's handle for the
+            // duration of the closure.
+            thread::set_current(their_thread);
             // SAFETY: we constructed `f` initialized.
             let f = f.into_inner();
             // Execute the closure and catch any panics
@@ -244,7 +279,9 @@ impl Builder {
         };
 
         if is_web_worker_thread() {
-            WorkerMessage::SpawnThread(BuilderRequest { builder: self, context }).post();
+            WorkerMessage::SpawnThread(BuilderRequest { builder: self, context, pooled }).post();
+        } else if pooled {
+            ThreadPool::global().spawn_context(context);
         } else {
             self.spawn_for_context(context);
         }
@@ -256,16 +293,22 @@ impl Builder {
         Ok(JoinInner {
             signal: my_signal,
             packet: my_packet,
+            thread,
         })
     }
 
-    unsafe fn spawn_for_context(self, ctx: WebWorkerContext) {
+    /// Creates a freshly spawned, idle `web_sys::Worker` configured according
+    /// to this builder (name/prefix, script, worker type), but does not post
+    /// an init message or attach an `onmessage` handler yet. Shared by the
+    /// one-shot path ([`Self::spawn_for_context`]) and [`pool::ThreadPool`],
+    /// which attaches its own persistent handler.
+    pub(crate) fn create_worker(&self) -> Rc<Worker> {
         let Builder {
             name,
             prefix,
             wasm_bindgen_shim_url,
             ..
-        } = self;
+        } = self.clone();
 
         // Get worker script as URL encoded blob
         let script = get_worker_script(wasm_bindgen_shim_url);
@@ -296,8 +339,11 @@ impl Builder {
             options.type_(WorkerType::Classic);
         }
 
-        // Spawn the worker
-        let worker = Rc::new(Worker::new_with_options(script.as_str(), &options).unwrap());
+        Rc::new(Worker::new_with_options(script.as_str(), &options).unwrap())
+    }
+
+    unsafe fn spawn_for_context(self, ctx: WebWorkerContext) {
+        let worker = self.create_worker();
 
         // Make copy and keep a reference in callback handler so that GC does not despawn worker
         let mut their_worker = Some(worker.clone());
@@ -321,23 +367,7 @@ impl Builder {
         // TODO: cleanup this leak somehow
         callback.forget();
 
-        let ctx_ptr = Box::into_raw(Box::new(ctx));
-
-        // Pack shared wasm (module and memory) and work as a single JS array
-        let init = js_sys::Array::new();
-        init.push(&wasm_bindgen::module());
-        init.push(&wasm_bindgen::memory());
-        init.push(&JsValue::from(ctx_ptr as u32));
-
-        // Send initialization message
-        match worker.post_message(&init) {
-            Ok(()) => Ok(worker),
-            Err(e) => {
-                drop(Box::from_raw(ctx_ptr));
-                Err(e)
-            }
-        }
-        .unwrap();
+        pool::post_init_message(&worker, ctx).unwrap();
     }
 }
 
@@ -396,6 +426,7 @@ impl<'scope, T> Drop for Packet<'scope, T> {
 pub(crate) struct JoinInner<'scope, T> {
     packet: Arc<Packet<'scope, T>>,
     signal: Arc<Signal>,
+    thread: Thread,
 }
 
 impl<'scope, T> JoinInner<'scope, T> {
@@ -408,6 +439,36 @@ impl<'scope, T> JoinInner<'scope, T> {
         self.signal.wait_async().await;
         Arc::get_mut(&mut self.packet).unwrap().result.get_mut().take().unwrap()
     }
+
+    /// Non-blocking check of whether the thread has finished running.
+    pub fn is_finished(&self) -> bool {
+        self.signal.is_signaled()
+    }
+
+    /// Waits for the thread to finish for at most `timeout`, returning it
+    /// back (so the caller can retry, poll, or give up) if it didn't.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called on the main browser thread, which is not allowed to
+    /// block.
+    pub fn join_timeout(mut self, timeout: Duration) -> std::result::Result<Result<T>, Self> {
+        if self.signal.wait_timeout(timeout.as_secs_f64() * 1000.0) {
+            Ok(Arc::get_mut(&mut self.packet).unwrap().result.get_mut().take().unwrap())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Async variant of [`Self::join_timeout`], for callers (e.g. on the main
+    /// thread) that can't block.
+    pub async fn join_async_timeout(mut self, timeout: Duration) -> std::result::Result<Result<T>, Self> {
+        if self.signal.wait_async_timeout(timeout.as_secs_f64() * 1000.0).await {
+            Ok(Arc::get_mut(&mut self.packet).unwrap().result.get_mut().take().unwrap())
+        } else {
+            Err(self)
+        }
+    }
 }
 
 /// An owned permission to join on a thread (block on its termination).
@@ -416,8 +477,7 @@ pub struct JoinHandle<T>(JoinInner<'static, T>);
 impl<T> JoinHandle<T> {
     /// Extracts a handle to the underlying thread.
     pub fn thread(&self) -> &Thread {
-        unimplemented!();
-        //&self.0.thread
+        &self.0.thread
     }
 
     /// Waits for the associated thread to finish.
@@ -429,6 +489,36 @@ impl<T> JoinHandle<T> {
     pub async fn join_async(self) -> Result<T> {
         self.0.join_async().await
     }
+
+    /// Checks whether the associated thread has finished running its
+    /// function, without blocking.
+    ///
+    /// This is a best-effort snapshot: a `false` result doesn't guarantee
+    /// the thread isn't about to finish, but a `true` result guarantees
+    /// [`Self::join`] won't block.
+    pub fn is_finished(&self) -> bool {
+        self.0.is_finished()
+    }
+
+    /// Waits for the associated thread to finish for at most `timeout`.
+    ///
+    /// Returns the thread's result if it finished in time, or hands the
+    /// handle back (so the caller can retry or give up) otherwise.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called on the main browser thread, which isn't allowed to
+    /// block - same restriction as [`Self::join`]. Use [`Self::join_async_timeout`]
+    /// there instead.
+    pub fn join_timeout(self, timeout: Duration) -> std::result::Result<Result<T>, JoinHandle<T>> {
+        self.0.join_timeout(timeout).map_err(JoinHandle)
+    }
+
+    /// Async variant of [`Self::join_timeout`], safe to call from the main
+    /// browser thread.
+    pub async fn join_async_timeout(self, timeout: Duration) -> std::result::Result<Result<T>, JoinHandle<T>> {
+        self.0.join_async_timeout(timeout).await.map_err(JoinHandle)
+    }
 }
 
 impl<T> fmt::Debug for JoinHandle<T> {
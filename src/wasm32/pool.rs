@@ -0,0 +1,433 @@
+//! A pool of persistent, reusable web workers.
+//!
+//! Spawning a [`web_sys::Worker`] is expensive in the browser: it has to
+//! fetch and compile the `wasm_bindgen` shim and re-share the wasm module
+//! and memory with it. [`Builder::spawn_for_context`][super::Builder] used to
+//! pay that cost on every single [`spawn`][super::spawn], throwing the
+//! worker away as soon as the job finished. `ThreadPool` instead pre-warms a
+//! fixed number of workers and keeps them alive, dispatching jobs to
+//! whichever worker is next idle, modeled on rayon-core's `Registry`.
+//!
+//! Workers can't block waiting for work, so scheduling is purely
+//! event-driven: a worker posts [`WorkerMessage::ThreadComplete`] when it's
+//! done, the pool either hands it the next queued job or returns its slot to
+//! the free list.
+//!
+//! Known limitation: that hand-back relies on the job's own code reaching
+//! `wasm_thread_entry_point`'s `ThreadComplete.post()`. A job whose panic
+//! payload itself panics on drop (the pre-existing double-panic path in
+//! `Packet`'s `Drop` impl) aborts the worker without ever posting back, so
+//! that slot is lost for the life of the pool instead of being reaped or
+//! replaced.
+
+use std::collections::VecDeque;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use wasm_bindgen::prelude::*;
+use web_sys::Worker;
+
+use super::signal::Signal;
+use super::utils::SpinLockMutex;
+use super::{available_parallelism, Builder, WebWorkerContext, WorkerMessage};
+
+/// Index of a worker within a [`ThreadPool`]'s registry.
+pub(crate) type SlotIndex = usize;
+
+struct Slot {
+    worker: Rc<Worker>,
+    /// Jobs destined for this specific worker (used by [`ThreadPool::broadcast`]),
+    /// checked before falling back to the pool-wide `pending` queue.
+    queue: VecDeque<WebWorkerContext>,
+}
+
+struct Registry {
+    slots: Vec<Slot>,
+    /// Slots that are idle and ready to receive a job immediately.
+    free: Vec<SlotIndex>,
+    /// Jobs waiting for a slot to free up.
+    pending: VecDeque<WebWorkerContext>,
+}
+
+// SAFETY: wasm is single-threaded within a given worker/the main thread, so
+// nothing here is ever actually accessed concurrently (unlike
+// `Signal`/`Parker` in signal.rs, which are shared across threads and rely
+// on `Atomics`). `Rc<Worker>` just needs an explicit opt-in since `Rc` is
+// never `Send`/`Sync` on its own.
+unsafe impl Send for Registry {}
+unsafe impl Sync for Registry {}
+
+/// A pool of persistent, reusable web workers that jobs can be dispatched to
+/// without paying worker startup cost on every call.
+pub struct ThreadPool {
+    registry: Arc<Mutex<Registry>>,
+}
+
+static DEFAULT_POOL: Mutex<Option<Arc<ThreadPool>>> = Mutex::new(None);
+
+impl ThreadPool {
+    /// Creates a new pool of `size` pre-warmed workers, each created from
+    /// `builder` (so a set name is shared by all of them as-is, and a
+    /// prefix alone gets a random suffix per worker).
+    pub fn new_with_builder(size: usize, builder: Builder) -> Arc<ThreadPool> {
+        let pool = Arc::new(ThreadPool {
+            registry: Arc::new(Mutex::new(Registry {
+                slots: Vec::with_capacity(size),
+                free: Vec::with_capacity(size),
+                pending: VecDeque::new(),
+            })),
+        });
+
+        for slot in 0..size {
+            pool.add_worker(slot, &builder);
+        }
+
+        pool
+    }
+
+    /// Creates a new pool of `size` pre-warmed workers.
+    pub fn new(size: usize) -> Arc<ThreadPool> {
+        Self::new_with_builder(size, Builder::new())
+    }
+
+    /// Returns the process-wide default pool, creating it (sized to
+    /// [`available_parallelism`]) on first use.
+    pub fn global() -> Arc<ThreadPool> {
+        DEFAULT_POOL
+            .lock_spin()
+            .unwrap()
+            .get_or_insert_with(|| Self::new(available_parallelism()))
+            .clone()
+    }
+
+    /// Replaces the process-wide default pool used by later
+    /// [`spawn`][super::spawn]s. Jobs already submitted stay on the previous
+    /// pool; calling this before the first spawn means the default pool is
+    /// never created.
+    pub fn set_global(pool: Arc<ThreadPool>) {
+        *DEFAULT_POOL.lock_spin().unwrap() = Some(pool);
+    }
+
+    /// Number of workers in this pool.
+    pub fn num_threads(&self) -> usize {
+        self.registry.lock_spin().unwrap().slots.len()
+    }
+
+    fn add_worker(self: &Arc<Self>, slot: SlotIndex, builder: &Builder) {
+        let worker = builder.create_worker();
+
+        {
+            let mut registry = self.registry.lock_spin().unwrap();
+            debug_assert_eq!(registry.slots.len(), slot);
+            registry.slots.push(Slot {
+                worker: worker.clone(),
+                queue: VecDeque::new(),
+            });
+            registry.free.push(slot);
+        }
+
+        let pool = self.clone();
+        let callback = Closure::wrap(Box::new(move |x: &web_sys::MessageEvent| {
+            // All u32 bits map to f64 mantissa so it's safe to cast like that.
+            let req = unsafe { Box::from_raw(x.data().as_f64().unwrap() as u32 as *mut WorkerMessage) };
+
+            match *req {
+                WorkerMessage::SpawnThread(builder) => unsafe { builder.spawn() },
+                WorkerMessage::ThreadComplete => pool.on_worker_ready(slot),
+            };
+        }) as Box<dyn FnMut(&web_sys::MessageEvent)>);
+        worker.set_onmessage(Some(callback.as_ref().unchecked_ref()));
+
+        // TODO: cleanup this leak somehow, see `Builder::spawn_for_context`.
+        callback.forget();
+    }
+
+    fn on_worker_ready(self: &Arc<Self>, slot: SlotIndex) {
+        let mut registry = self.registry.lock_spin().unwrap();
+        let Registry { slots, pending, .. } = &mut *registry;
+        let ctx = next_job_for_slot(&mut slots[slot].queue, pending);
+        match ctx {
+            Some(ctx) => {
+                let worker = registry.slots[slot].worker.clone();
+                drop(registry);
+                post_job_message(&worker, ctx).unwrap();
+            }
+            None => registry.free.push(slot),
+        }
+    }
+
+    /// Submits a job onto the pool: dispatched immediately to an idle
+    /// worker, or queued until one frees up.
+    pub(crate) fn spawn_context(self: &Arc<Self>, ctx: WebWorkerContext) {
+        let mut registry = self.registry.lock_spin().unwrap();
+        match registry.free.pop() {
+            Some(slot) => {
+                let worker = registry.slots[slot].worker.clone();
+                drop(registry);
+                post_job_message(&worker, ctx).unwrap();
+            }
+            None => registry.pending.push_back(ctx),
+        }
+    }
+
+    /// Submits a job to a specific worker: dispatched immediately if that
+    /// worker is idle, or queued in its own (per-slot) queue otherwise, so
+    /// it runs there next regardless of the pool-wide `pending` queue.
+    fn dispatch_to_slot(self: &Arc<Self>, slot: SlotIndex, ctx: WebWorkerContext) {
+        let mut registry = self.registry.lock_spin().unwrap();
+        if let Some(pos) = registry.free.iter().position(|&s| s == slot) {
+            registry.free.swap_remove(pos);
+            let worker = registry.slots[slot].worker.clone();
+            drop(registry);
+            post_job_message(&worker, ctx).unwrap();
+        } else {
+            registry.slots[slot].queue.push_back(ctx);
+        }
+    }
+
+    /// Submits a closure to run on the pool, without waiting for (or caring
+    /// about) its result. See [`super::spawn`] for a variant that returns a
+    /// joinable handle.
+    pub fn spawn<F>(self: &Arc<Self>, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.spawn_context(WebWorkerContext { func: Box::new(f) });
+    }
+
+    /// Runs `f` once on every worker in the pool, waits for all of them to
+    /// finish, and returns their results indexed by worker.
+    ///
+    /// Each invocation receives a [`BroadcastContext`] identifying which
+    /// worker it's running on. Useful for partitioning work across workers
+    /// or for per-worker initialization (seeding thread-locals, warming
+    /// caches).
+    ///
+    /// # Panics
+    ///
+    /// Panics if any worker's closure panics.
+    pub fn broadcast<F, T>(self: &Arc<Self>, f: F) -> Vec<T>
+    where
+        F: Fn(BroadcastContext) -> T + Send + Sync + 'static,
+        T: Send + 'static,
+    {
+        self.spawn_broadcast_handle(f)
+            .join()
+            .into_iter()
+            .map(|r| r.expect("a broadcast worker panicked"))
+            .collect()
+    }
+
+    /// Like [`Self::broadcast`], but waits without blocking the calling
+    /// thread - safe to call from the main browser thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any worker's closure panics.
+    pub async fn broadcast_async<F, T>(self: &Arc<Self>, f: F) -> Vec<T>
+    where
+        F: Fn(BroadcastContext) -> T + Send + Sync + 'static,
+        T: Send + 'static,
+    {
+        self.spawn_broadcast_handle(f)
+            .join_async()
+            .await
+            .into_iter()
+            .map(|r| r.expect("a broadcast worker panicked"))
+            .collect()
+    }
+
+    /// Like [`Self::broadcast`], but fires the closure on every worker
+    /// without waiting for (or caring about) the results.
+    pub fn spawn_broadcast<F>(self: &Arc<Self>, f: F)
+    where
+        F: Fn(BroadcastContext) + Send + Sync + 'static,
+    {
+        self.spawn_broadcast_handle(f);
+    }
+
+    fn spawn_broadcast_handle<F, T>(self: &Arc<Self>, f: F) -> BroadcastHandle<T>
+    where
+        F: Fn(BroadcastContext) -> T + Send + Sync + 'static,
+        T: Send + 'static,
+    {
+        let num_threads = self.num_threads();
+        let f = Arc::new(f);
+        let packet = Arc::new(BroadcastPacket {
+            results: Mutex::new((0..num_threads).map(|_| None).collect()),
+            remaining: AtomicUsize::new(num_threads),
+            signal: Signal::new(),
+        });
+
+        // An empty pool has no worker left to ever signal completion -
+        // signal right away so `join`/`join_async` return an empty `Vec`
+        // instead of hanging forever.
+        if num_threads == 0 {
+            packet.signal.signal();
+        }
+
+        for index in 0..num_threads {
+            let f = f.clone();
+            let packet = packet.clone();
+
+            let ctx = WebWorkerContext {
+                func: Box::new(move || {
+                    let result = catch_unwind(AssertUnwindSafe(|| f(BroadcastContext { index, num_threads })));
+                    packet.results.lock().unwrap()[index] = Some(result);
+                    if packet.remaining.fetch_sub(1, Ordering::AcqRel) == 1 {
+                        packet.signal.signal();
+                    }
+                }),
+            };
+
+            self.dispatch_to_slot(index, ctx);
+        }
+
+        BroadcastHandle { packet }
+    }
+}
+
+/// Identifies a single invocation of a [`ThreadPool::broadcast`]/[`ThreadPool::spawn_broadcast`]
+/// closure.
+#[derive(Debug, Clone, Copy)]
+pub struct BroadcastContext {
+    /// Index of the worker this invocation is running on, in `0..num_threads`.
+    pub index: usize,
+    /// Total number of workers the closure was broadcast to.
+    pub num_threads: usize,
+}
+
+struct BroadcastPacket<T> {
+    results: Mutex<Vec<Option<std::thread::Result<T>>>>,
+    remaining: AtomicUsize,
+    signal: Signal,
+}
+
+/// A handle to a pending [`ThreadPool::broadcast`] call.
+struct BroadcastHandle<T> {
+    packet: Arc<BroadcastPacket<T>>,
+}
+
+impl<T> BroadcastHandle<T> {
+    /// Blocks until every worker has reported completion.
+    fn join(self) -> Vec<std::thread::Result<T>> {
+        self.packet.signal.wait();
+        self.packet
+            .results
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .map(|r| r.take().unwrap())
+            .collect()
+    }
+
+    /// Waits for every worker to report completion without blocking the
+    /// calling thread.
+    async fn join_async(self) -> Vec<std::thread::Result<T>> {
+        self.packet.signal.wait_async().await;
+        self.packet
+            .results
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .map(|r| r.take().unwrap())
+            .collect()
+    }
+}
+
+/// Chooses the next job a slot that just went idle should run, if any: its
+/// own per-slot queue (used by [`ThreadPool::broadcast`] to pin a job to a
+/// specific worker) takes priority over the pool-wide `pending` queue.
+///
+/// Kept as a free function, independent of `Registry`'s `Worker` handles, so
+/// the scheduling order can be unit tested without a real browser.
+fn next_job_for_slot(
+    slot_queue: &mut VecDeque<WebWorkerContext>,
+    pending: &mut VecDeque<WebWorkerContext>,
+) -> Option<WebWorkerContext> {
+    slot_queue.pop_front().or_else(|| pending.pop_front())
+}
+
+/// Sends a worker its very first job: the init message carrying the shared
+/// wasm module + memory alongside the job's context pointer (see
+/// `get_worker_script`'s `onmessage`).
+pub(crate) fn post_init_message(worker: &Worker, ctx: WebWorkerContext) -> Result<(), JsValue> {
+    let ctx_ptr = Box::into_raw(Box::new(ctx));
+
+    let init = js_sys::Array::new();
+    init.push(&wasm_bindgen::module());
+    init.push(&wasm_bindgen::memory());
+    init.push(&JsValue::from(ctx_ptr as u32));
+
+    worker.post_message(&init).map_err(|e| {
+        drop(unsafe { Box::from_raw(ctx_ptr) });
+        e
+    })
+}
+
+/// Sends an already-initialized (pooled) worker its next job: just the bare
+/// context pointer, since the wasm module is already instantiated there.
+fn post_job_message(worker: &Worker, ctx: WebWorkerContext) -> Result<(), JsValue> {
+    let ctx_ptr = Box::into_raw(Box::new(ctx));
+
+    worker.post_message(&JsValue::from(ctx_ptr as u32)).map_err(|e| {
+        drop(unsafe { Box::from_raw(ctx_ptr) });
+        e
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    fn marked_ctx(marker: Arc<Mutex<Vec<u32>>>, id: u32) -> WebWorkerContext {
+        WebWorkerContext {
+            func: Box::new(move || marker.lock().unwrap().push(id)),
+        }
+    }
+
+    fn run(ctx: WebWorkerContext) {
+        (ctx.func)();
+    }
+
+    #[test]
+    fn slot_queue_takes_priority_over_pending() {
+        let marker = Arc::new(Mutex::new(Vec::new()));
+        let mut slot_queue = VecDeque::new();
+        let mut pending = VecDeque::new();
+
+        pending.push_back(marked_ctx(marker.clone(), 1));
+        slot_queue.push_back(marked_ctx(marker.clone(), 2));
+
+        let ctx = next_job_for_slot(&mut slot_queue, &mut pending).unwrap();
+        run(ctx);
+        assert_eq!(*marker.lock().unwrap(), vec![2]);
+        // The pool-wide job is still waiting; the slot's own queue didn't
+        // drop it.
+        assert_eq!(pending.len(), 1);
+    }
+
+    #[test]
+    fn falls_back_to_pending_when_slot_queue_is_empty() {
+        let marker = Arc::new(Mutex::new(Vec::new()));
+        let mut slot_queue = VecDeque::new();
+        let mut pending = VecDeque::new();
+
+        pending.push_back(marked_ctx(marker.clone(), 1));
+
+        let ctx = next_job_for_slot(&mut slot_queue, &mut pending).unwrap();
+        run(ctx);
+        assert_eq!(*marker.lock().unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn returns_none_when_both_queues_are_empty() {
+        let mut slot_queue = VecDeque::new();
+        let mut pending = VecDeque::new();
+        assert!(next_job_for_slot(&mut slot_queue, &mut pending).is_none());
+    }
+}
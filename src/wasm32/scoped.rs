@@ -0,0 +1,124 @@
+use std::{
+    fmt,
+    marker::PhantomData,
+    panic::{catch_unwind, resume_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use super::{Builder, JoinInner, Result};
+
+/// Shared state between a [`Scope`] and the threads spawned within it.
+pub(crate) struct ScopeData {
+    num_running_threads: AtomicUsize,
+    a_thread_panicked: Mutex<bool>,
+}
+
+impl ScopeData {
+    fn new() -> Self {
+        Self {
+            num_running_threads: AtomicUsize::new(0),
+            a_thread_panicked: Mutex::new(false),
+        }
+    }
+
+    pub(crate) fn increment_num_running_threads(&self) {
+        self.num_running_threads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn decrement_num_running_threads(&self, unhandled_panic: bool) {
+        if unhandled_panic {
+            *self.a_thread_panicked.lock().unwrap() = true;
+        }
+        self.num_running_threads.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// A scope to spawn scoped threads in.
+///
+/// See [`scope`] for details.
+pub struct Scope<'scope, 'env: 'scope> {
+    data: Arc<ScopeData>,
+    /// Invariant over 'scope, to make sure `'scope` can't shrink, which is
+    /// necessary for soundness.
+    ///
+    /// Is also used for variance on `'env`.
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+/// An owned permission to join on a scoped thread (block on its termination).
+///
+/// See [`Scope::spawn`] for details.
+pub struct ScopedJoinHandle<'scope, T>(JoinInner<'scope, T>);
+
+/// Creates a scope for spawning scoped threads.
+///
+/// Unlike non-scoped threads, scoped threads can borrow non-`'static` data
+/// from the outside the scope, as the scope guarantees all threads will be
+/// joined before it returns (either normally or because of a panic).
+pub fn scope<'env, F, T>(f: F) -> T
+where
+    F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T,
+{
+    let scope = Scope {
+        data: Arc::new(ScopeData::new()),
+        scope: PhantomData,
+        env: PhantomData,
+    };
+
+    let result = catch_unwind(AssertUnwindSafe(|| f(&scope)));
+
+    // Wait for all threads spawned within the scope to finish running.
+    while scope.data.num_running_threads.load(Ordering::Acquire) != 0 {
+        // Busy-waits on the counter instead of blocking in `Atomics.wait`
+        // (which `ScopedJoinHandle::join` uses, and which panics on the main
+        // browser thread).
+        std::hint::spin_loop();
+    }
+
+    match result {
+        Err(e) => resume_unwind(e),
+        Ok(_) if *scope.data.a_thread_panicked.lock().unwrap() => panic!("a scoped thread panicked"),
+        Ok(result) => result,
+    }
+}
+
+impl<'scope, 'env> Scope<'scope, 'env> {
+    /// Spawns a new thread inside of a scope, returning a [`ScopedJoinHandle`]
+    /// to it.
+    pub fn spawn<F, T>(&'scope self, f: F) -> ScopedJoinHandle<'scope, T>
+    where
+        F: FnOnce() -> T + Send + 'scope,
+        T: Send + 'scope,
+    {
+        let inner = unsafe { Builder::new().spawn_unchecked_(f, Some(self.data.clone())) }.expect("failed to spawn thread");
+        ScopedJoinHandle(inner)
+    }
+}
+
+impl<'scope, T> ScopedJoinHandle<'scope, T> {
+    /// Waits for the associated thread to finish.
+    pub fn join(self) -> Result<T> {
+        self.0.join()
+    }
+
+    /// Waits for the associated thread to finish asynchronously.
+    pub async fn join_async(self) -> Result<T> {
+        self.0.join_async().await
+    }
+}
+
+impl<'scope, T> fmt::Debug for ScopedJoinHandle<'scope, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("ScopedJoinHandle { .. }")
+    }
+}
+
+impl<'scope, 'env> fmt::Debug for Scope<'scope, 'env> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("Scope { .. }")
+    }
+}
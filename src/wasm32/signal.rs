@@ -0,0 +1,178 @@
+use js_sys::{Atomics, Int32Array, SharedArrayBuffer};
+use wasm_bindgen::prelude::*;
+
+const UNSIGNALED: i32 = 0;
+const SIGNALED: i32 = 1;
+
+/// A single-shot, thread-safe signal used to notify a waiter that some event
+/// (thread completion, a broadcast finishing on every worker, ...) has happened.
+///
+/// Backed by a one-element [`SharedArrayBuffer`] so that it can be waited on
+/// with `Atomics.wait` from a worker thread, and polled or waited on
+/// asynchronously from the main thread (which is not allowed to block).
+pub(crate) struct Signal {
+    view: Int32Array,
+}
+
+// SAFETY: `Int32Array` wraps a `SharedArrayBuffer`, which is shared across
+// threads by the browser; all access goes through `Atomics`.
+unsafe impl Send for Signal {}
+unsafe impl Sync for Signal {}
+
+impl Signal {
+    pub fn new() -> Self {
+        let buffer = SharedArrayBuffer::new(4);
+        let view = Int32Array::new(&buffer);
+        Self { view }
+    }
+
+    /// Sets the signal and wakes any thread waiting on it.
+    pub fn signal(&self) {
+        Atomics::store(&self.view, 0, SIGNALED).unwrap();
+        Atomics::notify(&self.view, 0).unwrap();
+    }
+
+    /// Returns `true` if [`Self::signal`] has already been called.
+    pub fn is_signaled(&self) -> bool {
+        Atomics::load(&self.view, 0).unwrap() == SIGNALED
+    }
+
+    /// Blocks the calling thread until the signal is set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called on the main browser thread, which is not allowed to
+    /// block.
+    pub fn wait(&self) {
+        while !self.is_signaled() {
+            Atomics::wait(&self.view, 0, UNSIGNALED).unwrap();
+        }
+    }
+
+    /// Blocks the calling thread until the signal is set or `timeout_ms`
+    /// elapses, whichever comes first.
+    ///
+    /// Returns `true` if the signal was set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called on the main browser thread, which is not allowed to
+    /// block.
+    pub fn wait_timeout(&self, timeout_ms: f64) -> bool {
+        if !self.is_signaled() {
+            Atomics::wait_with_timeout(&self.view, 0, UNSIGNALED, timeout_ms).unwrap();
+        }
+        self.is_signaled()
+    }
+
+    /// Waits for the signal to be set without blocking the current thread,
+    /// via `Atomics.waitAsync`. Intended for use from the main thread.
+    pub async fn wait_async(&self) {
+        self.wait_async_impl(None).await;
+    }
+
+    /// Like [`Self::wait_async`], but gives up (returning `false`) if the
+    /// signal hasn't been set within `timeout_ms`.
+    pub async fn wait_async_timeout(&self, timeout_ms: f64) -> bool {
+        self.wait_async_impl(Some(timeout_ms)).await;
+        self.is_signaled()
+    }
+
+    async fn wait_async_impl(&self, timeout_ms: Option<f64>) {
+        if self.is_signaled() {
+            return;
+        }
+
+        let result = match timeout_ms {
+            Some(ms) => Atomics::wait_async_with_timeout(&self.view, 0, UNSIGNALED, ms),
+            None => Atomics::wait_async(&self.view, 0, UNSIGNALED),
+        }
+        .unwrap();
+
+        if result.async_() {
+            let promise: js_sys::Promise = result.value().unchecked_into();
+            let _ = wasm_bindgen_futures::JsFuture::from(promise).await;
+        }
+    }
+}
+
+const EMPTY: i32 = 0;
+const NOTIFIED: i32 = 1;
+const PARKED: i32 = 2;
+
+/// The blocking half of `std::thread::park`/`unpark`'s token semantics,
+/// implemented on top of `Atomics.wait`/`notify` instead of a condvar.
+///
+/// A call to [`Self::unpark`] that races ahead of the matching [`Self::park`]
+/// is not lost (the token is left available for the next `park` to consume
+/// immediately), but multiple `unpark`s before a `park` don't accumulate into
+/// multiple tokens.
+pub(crate) struct Parker {
+    view: Int32Array,
+}
+
+unsafe impl Send for Parker {}
+unsafe impl Sync for Parker {}
+
+impl Parker {
+    pub fn new() -> Self {
+        let buffer = SharedArrayBuffer::new(4);
+        let view = Int32Array::new(&buffer);
+        Atomics::store(&view, 0, EMPTY).unwrap();
+        Self { view }
+    }
+
+    /// Blocks until a token is available, consuming it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called on the main browser thread, which is not allowed to
+    /// block.
+    pub fn park(&self) {
+        // A token is already available: consume it and return immediately.
+        if Atomics::compare_exchange(&self.view, 0, NOTIFIED, EMPTY).unwrap() == NOTIFIED {
+            return;
+        }
+
+        // Only this thread ever sets `PARKED`, so if this exchange fails an
+        // `unpark` has landed since the check above: consume its token
+        // rather than overwrite it.
+        if Atomics::compare_exchange(&self.view, 0, EMPTY, PARKED).unwrap() == NOTIFIED {
+            Atomics::store(&self.view, 0, EMPTY).unwrap();
+            return;
+        }
+        loop {
+            Atomics::wait(&self.view, 0, PARKED).unwrap();
+            if Atomics::compare_exchange(&self.view, 0, NOTIFIED, EMPTY).unwrap() == NOTIFIED {
+                return;
+            }
+            // Spurious wake (state is still `PARKED`): keep waiting.
+        }
+    }
+
+    /// Like [`Self::park`], but gives up after (at most) `timeout_ms`.
+    pub fn park_timeout(&self, timeout_ms: f64) {
+        if Atomics::compare_exchange(&self.view, 0, NOTIFIED, EMPTY).unwrap() == NOTIFIED {
+            return;
+        }
+
+        // Same race as in `park`: keep a token that arrived in between.
+        if Atomics::compare_exchange(&self.view, 0, EMPTY, PARKED).unwrap() == NOTIFIED {
+            Atomics::store(&self.view, 0, EMPTY).unwrap();
+            return;
+        }
+        Atomics::wait_with_timeout(&self.view, 0, PARKED, timeout_ms).unwrap();
+        // Whether we were notified or timed out, consume any token and
+        // leave the parker `EMPTY` rather than stuck `PARKED`.
+        Atomics::compare_exchange(&self.view, 0, NOTIFIED, EMPTY).unwrap();
+        Atomics::compare_exchange(&self.view, 0, PARKED, EMPTY).unwrap();
+    }
+
+    /// Makes a token available, waking a thread blocked in [`Self::park`] if
+    /// there is one.
+    pub fn unpark(&self) {
+        if Atomics::exchange(&self.view, 0, NOTIFIED).unwrap() == PARKED {
+            Atomics::notify(&self.view, 0).unwrap();
+        }
+    }
+}
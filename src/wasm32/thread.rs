@@ -0,0 +1,161 @@
+//! A minimal, wasm-native analogue of [`std::thread::Thread`]/[`std::thread::ThreadId`].
+//!
+//! std's versions don't carry the name/prefix a [`super::Builder`] already
+//! stores, and there's no way to construct one for a worker that wasn't
+//! spawned through `std::thread::spawn`. This gives every thread spawned
+//! through this crate (and the main browser thread, lazily) a real identity,
+//! and implements std's token-based `park`/`unpark` on top of [`Parker`].
+
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::signal::Parker;
+
+/// A unique identifier for a running thread, assigned on [`Thread`] creation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ThreadId(u64);
+
+impl ThreadId {
+    fn new() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        ThreadId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+struct Inner {
+    id: ThreadId,
+    name: Option<String>,
+    parker: Parker,
+}
+
+/// A handle to a thread, carrying its name/id and providing `park`/`unpark`.
+///
+/// Obtained through [`current`] or [`JoinHandle::thread`][super::JoinHandle::thread].
+#[derive(Clone)]
+pub struct Thread(Arc<Inner>);
+
+impl Thread {
+    /// Creates a new, uniquely-identified thread handle, combining `name`
+    /// and `prefix` as `prefix:name` like [`super::Builder::create_worker`]
+    /// does for the underlying worker's display name. With only a prefix,
+    /// this handle uses its own id as the suffix, whereas the worker's
+    /// display name uses a random number.
+    pub(crate) fn new(name: Option<String>, prefix: Option<String>) -> Self {
+        let id = ThreadId::new();
+        let name = match (name, prefix) {
+            (Some(name), Some(prefix)) => Some(format!("{}:{}", prefix, name)),
+            (Some(name), None) => Some(name),
+            (None, Some(prefix)) => Some(format!("{}:{}", prefix, id.0)),
+            (None, None) => None,
+        };
+
+        Thread(Arc::new(Inner {
+            id,
+            name,
+            parker: Parker::new(),
+        }))
+    }
+
+    /// Returns this thread's identifier. Unique for the lifetime of the
+    /// program.
+    pub fn id(&self) -> ThreadId {
+        self.0.id
+    }
+
+    /// Returns this thread's name, as set via [`super::Builder::name`]/[`super::Builder::prefix`],
+    /// or `None` if neither was set.
+    pub fn name(&self) -> Option<&str> {
+        self.0.name.as_deref()
+    }
+
+    /// Atomically makes this thread's token available, waking it if it's
+    /// currently blocked in [`park`]/[`park_timeout`].
+    ///
+    /// An `unpark` that races ahead of the matching `park` is not lost: the
+    /// next `park` call on this thread returns immediately. Tokens don't
+    /// accumulate - multiple `unpark`s before a `park` are equivalent to one.
+    pub fn unpark(&self) {
+        self.0.parker.unpark();
+    }
+}
+
+impl fmt::Debug for Thread {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Thread").field("id", &self.0.id).field("name", &self.0.name).finish()
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Thread>> = const { RefCell::new(None) };
+}
+
+/// Sets the handle [`current`] returns on the calling thread. Called once,
+/// right before a spawned thread's closure starts running.
+pub(crate) fn set_current(thread: Thread) {
+    CURRENT.with(|c| *c.borrow_mut() = Some(thread));
+}
+
+/// Returns a handle to the thread that invokes it.
+///
+/// Threads spawned through this crate have this set up automatically; any
+/// other thread (typically the main browser thread) gets a lazily-created,
+/// unnamed handle on first access.
+pub fn current() -> Thread {
+    CURRENT.with(|c| c.borrow_mut().get_or_insert_with(|| Thread::new(None, None)).clone())
+}
+
+/// Blocks the current thread unless or until its token is made available by
+/// a call to [`Thread::unpark`] on its [`Thread`] handle.
+///
+/// # Panics
+///
+/// Panics if called on the main browser thread, which isn't allowed to
+/// block.
+pub fn park() {
+    current().0.parker.park();
+}
+
+/// Like [`park`], but only blocks for (at most) `dur`.
+pub fn park_timeout(dur: Duration) {
+    current().0.parker.park_timeout(dur.as_secs_f64() * 1000.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thread_ids_are_unique() {
+        let a = Thread::new(None, None);
+        let b = Thread::new(None, None);
+        assert_ne!(a.id(), b.id());
+    }
+
+    #[test]
+    fn name_prefers_name_and_prefix_combined() {
+        let thread = Thread::new(Some("worker".into()), Some("pool".into()));
+        assert_eq!(thread.name(), Some("pool:worker"));
+    }
+
+    #[test]
+    fn name_falls_back_to_plain_name() {
+        let thread = Thread::new(Some("worker".into()), None);
+        assert_eq!(thread.name(), Some("worker"));
+    }
+
+    #[test]
+    fn name_falls_back_to_prefix_and_id() {
+        let thread = Thread::new(None, Some("pool".into()));
+        let name = thread.name().unwrap();
+        assert_eq!(name, format!("pool:{}", thread.id().0));
+    }
+
+    #[test]
+    fn name_is_none_without_name_or_prefix() {
+        let thread = Thread::new(None, None);
+        assert_eq!(thread.name(), None);
+    }
+}
@@ -0,0 +1,204 @@
+//! Cooperative thread-local storage for pooled/reused workers.
+//!
+//! `std::thread_local!` values only run their destructor when the underlying
+//! OS thread exits, but under [`super::ThreadPool`] a worker's underlying
+//! thread never exits between jobs - so such values would leak state from
+//! one job into the next. [`worker_local!`] mirrors `std::thread_local!`'s
+//! API, except the value is dropped and reset for re-initialization at the
+//! end of every job [`wasm_thread_entry_point`][super::wasm_thread_entry_point]
+//! runs, not just on real thread exit.
+
+use std::cell::RefCell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::thread::LocalKey;
+
+type Destructor = Box<dyn FnMut()>;
+
+std::thread_local! {
+    static DESTRUCTORS: RefCell<Vec<Destructor>> = RefCell::new(Vec::new());
+}
+
+fn register_destructor(d: Destructor) {
+    DESTRUCTORS.with(|ds| ds.borrow_mut().push(d));
+}
+
+/// Runs every destructor registered on the current (real) thread since the
+/// last time this ran, in reverse registration order, then does it again if
+/// any of them registered new worker-locals of their own - bounded the same
+/// way std bounds re-registration during real thread-local teardown, so a
+/// destructor that keeps re-registering can't loop forever.
+///
+/// A panicking destructor is caught so it can't stop the rest from running.
+pub(crate) fn run_destructors() {
+    const MAX_ROUNDS: u32 = 8;
+
+    for _ in 0..MAX_ROUNDS {
+        let pending = DESTRUCTORS.with(|ds| std::mem::take(&mut *ds.borrow_mut()));
+        if pending.is_empty() {
+            return;
+        }
+
+        for mut destructor in pending.into_iter().rev() {
+            let _ = catch_unwind(AssertUnwindSafe(|| destructor()));
+        }
+    }
+}
+
+/// The per-thread storage backing a [`worker_local!`] static: an optional
+/// value plus the constructor used to (re-)create it.
+pub struct WorkerLocalCell<T: 'static> {
+    value: RefCell<Option<T>>,
+    init: Box<dyn Fn() -> T>,
+}
+
+impl<T: 'static> WorkerLocalCell<T> {
+    pub fn new(init: impl Fn() -> T + 'static) -> Self {
+        Self {
+            value: RefCell::new(None),
+            init: Box::new(init),
+        }
+    }
+
+    /// Runs `f` with this thread's value, initializing it first if this is
+    /// the first access since the last time it was reset (by a prior job's
+    /// cleanup, or because this is the first job this thread has run).
+    pub fn get<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        if self.value.borrow().is_none() {
+            *self.value.borrow_mut() = Some((self.init)());
+
+            // SAFETY: `self` lives in this real thread's TLS storage, which
+            // outlives any single job even though the worker may be reused
+            // for more jobs afterwards; `run_destructors` only ever runs on
+            // the thread that registered this destructor, and always before
+            // that thread is handed its next job.
+            let ptr: *const WorkerLocalCell<T> = self;
+            register_destructor(Box::new(move || {
+                let this = unsafe { &*ptr };
+                *this.value.borrow_mut() = None;
+            }));
+        }
+
+        f(self.value.borrow().as_ref().unwrap())
+    }
+}
+
+/// A handle to a [`worker_local!`] static, giving it `std::thread::LocalKey`-like
+/// `with` ergonomics in a single call instead of having to reach through the
+/// underlying [`WorkerLocalCell`].
+pub struct WorkerLocalRef<T: 'static> {
+    key: &'static LocalKey<WorkerLocalCell<T>>,
+}
+
+impl<T: 'static> WorkerLocalRef<T> {
+    #[doc(hidden)]
+    pub fn new(key: &'static LocalKey<WorkerLocalCell<T>>) -> Self {
+        Self { key }
+    }
+
+    /// Runs `f` with a reference to this thread's value.
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        self.key.with(|cell| cell.get(f))
+    }
+}
+
+/// Declares a cooperative, worker-local value.
+///
+/// ```ignore
+/// worker_local! {
+///     static COUNTER: std::cell::Cell<u32> = std::cell::Cell::new(0);
+/// }
+///
+/// COUNTER().with(|c| c.set(c.get() + 1));
+/// ```
+///
+/// Unlike `std::thread_local!`'s `COUNTER`, this one is reset (the old value
+/// dropped) at the end of every job the current worker runs, not just when
+/// the underlying OS thread exits - which matters once workers are pooled
+/// and reused by [`ThreadPool`][super::ThreadPool].
+#[macro_export]
+macro_rules! worker_local {
+    ($(#[$attr:meta])* $vis:vis static $name:ident: $t:ty = $init:expr;) => {
+        $(#[$attr])*
+        $vis fn $name() -> $crate::WorkerLocalRef<$t> {
+            ::std::thread_local! {
+                static CELL: $crate::WorkerLocalCell<$t> = $crate::WorkerLocalCell::new(|| $init);
+            }
+            $crate::WorkerLocalRef::new(&CELL)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+
+    // `DESTRUCTORS` is real `std::thread_local!` state shared by every test
+    // in this process's thread, so each test needs to leave it empty.
+    fn drain() {
+        run_destructors();
+    }
+
+    #[test]
+    fn destructors_run_in_reverse_registration_order() {
+        drain();
+        let order = Rc::new(RefCell::new(Vec::new()));
+
+        for i in 0..3 {
+            let order = order.clone();
+            register_destructor(Box::new(move || order.borrow_mut().push(i)));
+        }
+
+        run_destructors();
+        assert_eq!(*order.borrow(), vec![2, 1, 0]);
+    }
+
+    #[test]
+    fn reregistration_during_teardown_is_bounded() {
+        drain();
+        let rounds = Rc::new(RefCell::new(0));
+
+        // Keeps re-registering itself well past `MAX_ROUNDS`, so the
+        // assertion below only holds if `run_destructors` actually stops
+        // calling it rather than looping forever.
+        fn register_self(rounds: Rc<RefCell<u32>>) {
+            register_destructor(Box::new(move || {
+                *rounds.borrow_mut() += 1;
+                if *rounds.borrow() < 100 {
+                    register_self(rounds.clone());
+                }
+            }));
+        }
+        register_self(rounds.clone());
+
+        run_destructors();
+        assert_eq!(*rounds.borrow(), 8);
+
+        // `run_destructors` gave up with one registration still pending;
+        // let it finish so it doesn't bleed into later tests on this thread.
+        while DESTRUCTORS.with(|ds| !ds.borrow().is_empty()) {
+            run_destructors();
+        }
+        assert_eq!(*rounds.borrow(), 100);
+    }
+
+    #[test]
+    fn worker_local_cell_resets_between_jobs() {
+        drain();
+        let cell = WorkerLocalCell::new(|| Rc::new(RefCell::new(0)));
+
+        cell.get(|v| *v.borrow_mut() += 1);
+        cell.get(|v| *v.borrow_mut() += 1);
+        assert_eq!(cell.get(|v| *v.borrow()), 2);
+
+        run_destructors();
+
+        // A fresh access after teardown re-initializes rather than reusing
+        // the old value.
+        assert_eq!(cell.get(|v| *v.borrow()), 0);
+
+        drain();
+    }
+}
@@ -0,0 +1,100 @@
+use std::sync::{Mutex, MutexGuard};
+
+use wasm_bindgen::prelude::*;
+use web_sys::DedicatedWorkerGlobalScope;
+
+/// Extension trait providing a spin-locking variant of [`Mutex::lock`].
+///
+/// Wasm has no OS-level blocking primitive available on the main browser
+/// thread, so anything that might be locked from there (e.g. [`DEFAULT_BUILDER`][super::DEFAULT_BUILDER])
+/// is taken with a short busy-loop instead of parking.
+pub(crate) trait SpinLockMutex<T> {
+    fn lock_spin(&self) -> Result<MutexGuard<T>, std::sync::TryLockError<MutexGuard<T>>>;
+}
+
+impl<T> SpinLockMutex<T> for Mutex<T> {
+    fn lock_spin(&self) -> Result<MutexGuard<T>, std::sync::TryLockError<MutexGuard<T>>> {
+        loop {
+            match self.try_lock() {
+                Ok(guard) => return Ok(guard),
+                Err(std::sync::TryLockError::WouldBlock) => continue,
+                Err(e @ std::sync::TryLockError::Poisoned(_)) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Returns the number of logical cores available to the browser, as reported
+/// by `navigator.hardwareConcurrency`.
+///
+/// Falls back to `1` if the value is unavailable (e.g. in some worker
+/// contexts that don't expose `navigator`).
+pub fn available_parallelism() -> usize {
+    js_sys::eval("navigator.hardwareConcurrency")
+        .ok()
+        .and_then(|v| v.as_f64())
+        .map(|v| v as usize)
+        .unwrap_or(1)
+}
+
+/// Returns `true` if currently running inside a web worker (as opposed to
+/// the main browser thread).
+pub fn is_web_worker_thread() -> bool {
+    js_sys::global().dyn_into::<DedicatedWorkerGlobalScope>().is_ok()
+}
+
+/// Returns the URL of the `wasm_bindgen` generated shim `.js` script for the
+/// currently executing module.
+///
+/// This is used as the default when [`super::Builder::wasm_bindgen_shim_url`]
+/// is not set, and relies on `import.meta.url` being available, which is the
+/// case for the `web` and `bundler` `wasm_bindgen` targets.
+pub fn get_wasm_bindgen_shim_script_path() -> String {
+    js_sys::eval("import.meta.url").unwrap().as_string().unwrap()
+}
+
+/// Builds the worker entry point script and returns it as a `Blob` URL
+/// suitable for passing to `Worker::new`.
+///
+/// The generated script imports the `wasm_bindgen` shim and, on the first
+/// message it receives (`[module, memory, ctx_ptr]`), initializes it with the
+/// module and memory shared by the spawning thread and runs the job pointed
+/// to by `ctx_ptr`. Once initialized it replaces `self.onmessage` with a
+/// lighter handler that just forwards the (now bare) `ctx_ptr` of later
+/// messages straight to [`wasm_thread_entry_point`][super::wasm_thread_entry_point],
+/// so that a worker kept alive by [`super::pool::ThreadPool`] doesn't pay the
+/// cost of re-instantiating the module for every job it runs.
+pub fn get_worker_script(wasm_bindgen_shim_url: Option<String>) -> String {
+    let shim_url = wasm_bindgen_shim_url.unwrap_or_else(get_wasm_bindgen_shim_script_path);
+
+    let script = format!(
+        r#"
+        importScripts('{shim_url}');
+
+        self.onmessage = (event) => {{
+            let [module, memory, ctx_ptr] = event.data;
+            wasm_bindgen(module, memory).then(() => {{
+                self.onmessage = (event) => {{
+                    wasm_bindgen.wasm_thread_entry_point(event.data);
+                }};
+                wasm_bindgen.wasm_thread_entry_point(ctx_ptr);
+            }});
+        }};
+        "#,
+        shim_url = shim_url,
+    );
+
+    let array = js_sys::Array::new();
+    array.push(&JsValue::from_str(&script));
+
+    let blob = web_sys::Blob::new_with_str_sequence(&array).unwrap();
+    web_sys::Url::create_object_url_with_blob(&blob).unwrap()
+}
+
+/// Loads the `module-workers-polyfill` shim, required by some browsers to
+/// spawn `type: "module"` workers. Only used when building with the
+/// `es_modules` feature.
+#[cfg(feature = "es_modules")]
+pub(crate) fn load_module_workers_polyfill() {
+    js_sys::eval(include_str!("module_workers_polyfill.js")).unwrap();
+}